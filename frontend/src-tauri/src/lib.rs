@@ -1,7 +1,16 @@
+mod sidecar;
+mod sidecar_config;
+
+use tauri::Manager;
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
   tauri::Builder::default()
     .plugin(tauri_plugin_shell::init())
+    .invoke_handler(tauri::generate_handler![
+      sidecar::sidecar_port,
+      sidecar::write_sidecar
+    ])
     .setup(|app| {
       if cfg!(debug_assertions) {
         app.handle().plugin(
@@ -10,17 +19,32 @@ pub fn run() {
             .build(),
         )?;
       }
-      // Sidecar is automatically handled by the shell plugin if configured in tauri.conf.json
-      // But we can also explicitly spawn it here if needed for more control.
-      // For now, let's rely on the shell plugin which we need to add.
-      
-      // Actually, for a persistent sidecar like a server, we usually spawn it here.
-      use tauri_plugin_shell::ShellExt;
-      let sidecar_command = app.shell().sidecar("api").unwrap();
-      let (mut _rx, _child) = sidecar_command.spawn().expect("Failed to spawn sidecar");
-      
+
+      // Loaded here (rather than before the builder runs) because reading
+      // the bundled sidecar.json resource needs an AppHandle to resolve it.
+      let sidecars = sidecar_config::load(app.handle()).sidecars;
+      app.manage(sidecar::SidecarState::new(sidecars.len()));
+
+      // Stay hidden until every configured sidecar reports itself ready;
+      // supervise_all() shows the window once they've all checked in.
+      if let Some(window) = app.get_webview_window("main") {
+        window.hide()?;
+      }
+
+      sidecar::supervise_all(app.handle().clone(), sidecars);
+
       Ok(())
     })
-    .run(tauri::generate_context!())
-    .expect("error while running tauri application");
+    .build(tauri::generate_context!())
+    .expect("error while building tauri application")
+    .run(|app, event| {
+      // Make sure no sidecar outlives the window, e.g. holding its port open
+      // after the user closes the app mid-request.
+      if matches!(
+        event,
+        tauri::RunEvent::ExitRequested { .. } | tauri::RunEvent::Exit
+      ) {
+        app.state::<sidecar::SidecarState>().shutdown_all();
+      }
+    });
 }
@@ -0,0 +1,458 @@
+//! Supervision for configured sidecar processes.
+//!
+//! Each sidecar described in `sidecar.json` (see [`crate::sidecar_config`])
+//! gets its own spawn/backoff/restart loop; runtime state for all of them is
+//! tracked in a single [`SidecarState`] keyed by name so commands and the
+//! readiness gate can address a specific one.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_shell::process::{CommandChild, CommandEvent};
+use tauri_plugin_shell::ShellExt;
+
+use crate::sidecar_config::SidecarConfig;
+
+/// Initial delay before the first restart attempt.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+/// Upper bound on the restart delay once it has doubled a few times.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// If a sidecar stays up at least this long, the backoff and attempt counter
+/// are reset on its next crash, so a flaky restart years ago doesn't count
+/// against a sidecar that's been healthy since.
+const HEALTHY_THRESHOLD: Duration = Duration::from_secs(60);
+/// Prefix of the stdout line a sidecar prints once its HTTP listener is up,
+/// e.g. `READY 51823`.
+const READY_PREFIX: &str = "READY ";
+/// How long to wait for a sidecar's readiness marker before giving up on it.
+const READY_TIMEOUT: Duration = Duration::from_secs(15);
+/// Event a sidecar's stdout lines are re-emitted under.
+const STDOUT_EVENT: &str = "sidecar://stdout";
+/// Event a sidecar's stderr lines are re-emitted under.
+const STDERR_EVENT: &str = "sidecar://stderr";
+/// Event fired every time a sidecar (re)announces its port, including after
+/// a crash restart, so the frontend can always reconnect.
+const READY_EVENT: &str = "sidecar://ready";
+/// Event fired when a sidecar hits an unrecoverable startup or restart
+/// failure. Unlike `log::error!`, this reaches the frontend even in release
+/// builds where no logger is registered.
+const FATAL_EVENT: &str = "sidecar://fatal";
+
+/// A line bridged from a sidecar's stdout/stderr, tagged with which sidecar
+/// it came from so the frontend can tell multiple backends apart.
+#[derive(Clone, Serialize)]
+struct SidecarLine<'a> {
+    name: &'a str,
+    line: String,
+}
+
+/// Payload for [`READY_EVENT`].
+#[derive(Clone, Serialize)]
+struct SidecarReady<'a> {
+    name: &'a str,
+    port: u16,
+}
+
+/// Payload for [`FATAL_EVENT`].
+#[derive(Clone, Serialize)]
+struct SidecarFatal<'a> {
+    name: &'a str,
+    message: String,
+}
+
+/// Per-sidecar runtime state.
+#[derive(Default)]
+struct SidecarEntry {
+    child: Mutex<Option<CommandChild>>,
+    ready_port: Mutex<Option<u16>>,
+    /// Set once the app has asked this sidecar to exit, so the supervisor
+    /// knows a subsequent `Terminated` event is expected and shouldn't
+    /// trigger a restart.
+    shutting_down: AtomicBool,
+}
+
+/// Managed state tracking every configured sidecar by name.
+pub struct SidecarState {
+    entries: Mutex<HashMap<String, Arc<SidecarEntry>>>,
+    /// Number of sidecars still waiting to report readiness; the main
+    /// window is shown once this reaches zero.
+    pending_ready: AtomicUsize,
+}
+
+impl SidecarState {
+    /// Creates empty state for `sidecar_count` configured sidecars.
+    pub fn new(sidecar_count: usize) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            pending_ready: AtomicUsize::new(sidecar_count),
+        }
+    }
+
+    fn entry(&self, name: &str) -> Option<Arc<SidecarEntry>> {
+        self.entries.lock().unwrap().get(name).cloned()
+    }
+
+    fn entry_or_insert(&self, name: &str) -> Arc<SidecarEntry> {
+        self.entries
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_insert_with(Arc::default)
+            .clone()
+    }
+
+    /// Kills every running sidecar and marks them as intentionally stopped
+    /// so their supervisors don't try to bring them back.
+    pub fn shutdown_all(&self) {
+        for entry in self.entries.lock().unwrap().values() {
+            entry.shutting_down.store(true, Ordering::SeqCst);
+            if let Some(child) = entry.child.lock().unwrap().take() {
+                if let Err(err) = child.kill() {
+                    log::error!("failed to kill sidecar: {err}");
+                }
+            }
+        }
+    }
+
+    /// Records that `name` became ready on `port` and notifies the
+    /// frontend. Called on every successful spawn, not just the first, so a
+    /// sidecar that restarts on a new port is always reachable again. Only a
+    /// sidecar's first report (when it had no previously known port) counts
+    /// toward showing the main window, which is shown once every configured
+    /// sidecar has reported in.
+    fn note_ready(&self, app: &AppHandle, name: &str, port: u16) {
+        let entry = self.entry_or_insert(name);
+        let had_previous_port = entry.ready_port.lock().unwrap().replace(port).is_some();
+
+        if let Err(err) = app.emit(READY_EVENT, SidecarReady { name, port }) {
+            log::error!("failed to emit {READY_EVENT} for sidecar '{name}': {err}");
+        }
+
+        if !had_previous_port && self.pending_ready.fetch_sub(1, Ordering::SeqCst) == 1 {
+            if let Some(window) = app.get_webview_window("main") {
+                if let Err(err) = window.show() {
+                    log::error!("failed to show main window: {err}");
+                }
+            }
+        }
+    }
+}
+
+/// Spawns a supervisor for each configured sidecar.
+///
+/// The main window is expected to start hidden; `setup` hides it and each
+/// sidecar's first successful readiness contributes to showing it again
+/// once every configured sidecar has reported in at least once.
+pub fn supervise_all(app: AppHandle, sidecars: Vec<SidecarConfig>) {
+    if sidecars.is_empty() {
+        if let Some(window) = app.get_webview_window("main") {
+            if let Err(err) = window.show() {
+                log::error!("failed to show main window: {err}");
+            }
+        }
+    }
+
+    for config in sidecars {
+        supervise(app.clone(), config);
+    }
+}
+
+/// Spawns `config`'s sidecar and supervises it for the lifetime of the app.
+///
+/// If it fails to spawn or terminates unexpectedly, it is retried with
+/// exponential backoff, starting at [`INITIAL_BACKOFF`] and capping at
+/// [`MAX_BACKOFF`]. Once `config.max_restart_attempts` consecutive restarts
+/// have also failed, it gives up and surfaces a fatal error instead of
+/// retrying indefinitely.
+fn supervise(app: AppHandle, config: SidecarConfig) {
+    spawn_startup_watchdog(app.clone(), config.name.clone());
+
+    tauri::async_runtime::spawn(async move {
+        let mut backoff = INITIAL_BACKOFF;
+        let mut attempts: u32 = 0;
+
+        loop {
+            if app
+                .state::<SidecarState>()
+                .entry(&config.name)
+                .is_some_and(|entry| entry.shutting_down.load(Ordering::SeqCst))
+            {
+                return;
+            }
+
+            let port = match allocate_port_if_needed(&config) {
+                Ok(port) => port,
+                Err(err) => {
+                    log::error!(
+                        "failed to allocate a port for sidecar '{}': {err}",
+                        config.name
+                    );
+                    None
+                }
+            };
+
+            let mut rx = match spawn_sidecar(&app, &config, port) {
+                Ok(rx) => rx,
+                Err(err) => {
+                    log::error!("failed to spawn sidecar '{}': {err}", config.name);
+                    if !restart_or_give_up(&app, &config, &mut attempts, &mut backoff).await {
+                        return;
+                    }
+                    continue;
+                }
+            };
+
+            let started_at = Instant::now();
+            run_until_exit(&app, &config.name, &mut rx).await;
+
+            let entry = app.state::<SidecarState>().entry_or_insert(&config.name);
+            if let Some(child) = entry.child.lock().unwrap().take() {
+                if let Err(err) = child.kill() {
+                    log::error!("failed to kill sidecar '{}': {err}", config.name);
+                }
+            }
+            if entry.shutting_down.load(Ordering::SeqCst) {
+                return;
+            }
+
+            if started_at.elapsed() >= HEALTHY_THRESHOLD {
+                backoff = INITIAL_BACKOFF;
+                attempts = 0;
+            }
+
+            if !restart_or_give_up(&app, &config, &mut attempts, &mut backoff).await {
+                return;
+            }
+        }
+    });
+}
+
+/// Accounts for one failed attempt at running `config`'s sidecar, whether it
+/// failed to spawn in the first place or exited after running for a while.
+/// Sleeps for the current backoff (doubling it, capped at [`MAX_BACKOFF`])
+/// and returns `true` to retry, or — once `config.max_restart_attempts` is
+/// exceeded — surfaces a fatal error and returns `false` so the caller stops
+/// supervising this sidecar.
+async fn restart_or_give_up(
+    app: &AppHandle,
+    config: &SidecarConfig,
+    attempts: &mut u32,
+    backoff: &mut Duration,
+) -> bool {
+    *attempts += 1;
+    let attempt = *attempts;
+
+    if attempt > config.max_restart_attempts {
+        emit_fatal(
+            app,
+            &config.name,
+            format!(
+                "sidecar '{}' failed {attempt} times in a row, giving up on restarts",
+                config.name
+            ),
+        );
+        return false;
+    }
+
+    log::warn!(
+        "sidecar '{}' restarting in {:?} (attempt {attempt}/{})",
+        config.name,
+        backoff,
+        config.max_restart_attempts
+    );
+    tokio::time::sleep(*backoff).await;
+    *backoff = (*backoff * 2).min(MAX_BACKOFF);
+    true
+}
+
+/// After [`READY_TIMEOUT`], surfaces a fatal error if `name`'s sidecar has
+/// never reported a ready port, e.g. it's stuck restarting or never prints
+/// its readiness marker. It only checks the port recorded by
+/// [`SidecarState::note_ready`] and consumes nothing from the event stream;
+/// it runs once per supervised sidecar, not once per restart.
+fn spawn_startup_watchdog(app: AppHandle, name: String) {
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(READY_TIMEOUT).await;
+
+        let became_ready = app
+            .state::<SidecarState>()
+            .entry(&name)
+            .is_some_and(|entry| entry.ready_port.lock().unwrap().is_some());
+
+        if !became_ready {
+            emit_fatal(
+                &app,
+                &name,
+                format!("sidecar '{name}' did not become ready within {READY_TIMEOUT:?}"),
+            );
+        }
+    });
+}
+
+/// Allocates a free TCP port for `config` if it declares one, returning
+/// `None` for sidecars that don't need one.
+fn allocate_port_if_needed(config: &SidecarConfig) -> io::Result<Option<u16>> {
+    if config.port.is_some() {
+        allocate_free_port().map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+/// Binds to an ephemeral port and immediately releases it, returning the
+/// port number for the caller to pass to a child process.
+fn allocate_free_port() -> io::Result<u16> {
+    let listener = TcpListener::bind(("127.0.0.1", 0))?;
+    listener.local_addr().map(|addr| addr.port())
+}
+
+/// Spawns `config`'s sidecar, injecting `port` as both a CLI arg and an
+/// environment variable per its `port` config, and stores the resulting
+/// child handle in managed state.
+fn spawn_sidecar(
+    app: &AppHandle,
+    config: &SidecarConfig,
+    port: Option<u16>,
+) -> tauri_plugin_shell::Result<tokio::sync::mpsc::Receiver<CommandEvent>> {
+    let mut command = app
+        .shell()
+        .sidecar(&config.name)?
+        .args(&config.args)
+        .envs(config.env.clone());
+
+    if let (Some(port_config), Some(port)) = (&config.port, port) {
+        command = command
+            .args([format!("--{}", port_config.arg), port.to_string()])
+            .env(&port_config.env, port.to_string());
+    }
+
+    let (rx, child) = command.spawn()?;
+    app.state::<SidecarState>()
+        .entry_or_insert(&config.name)
+        .child
+        .lock()
+        .unwrap()
+        .replace(child);
+    Ok(rx)
+}
+
+/// Drains a sidecar's events until it terminates or errors out, noting
+/// readiness (on every spawn, not just the first) and bridging stdout/stderr
+/// lines to the webview as they arrive.
+async fn run_until_exit(app: &AppHandle, name: &str, rx: &mut tokio::sync::mpsc::Receiver<CommandEvent>) {
+    while let Some(event) = rx.recv().await {
+        match event {
+            CommandEvent::Stdout(line) => {
+                if let Some(port) = parse_ready_port(&line) {
+                    app.state::<SidecarState>().note_ready(app, name, port);
+                }
+                emit_line(app, name, STDOUT_EVENT, &line);
+            }
+            CommandEvent::Stderr(line) => {
+                emit_line(app, name, STDERR_EVENT, &line);
+            }
+            CommandEvent::Terminated(payload) => {
+                log::warn!("sidecar '{name}' terminated: {payload:?}");
+                return;
+            }
+            CommandEvent::Error(err) => {
+                log::error!("sidecar '{name}' error: {err}");
+                return;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Parses a `READY <port>` line, returning the port if the marker matches.
+fn parse_ready_port(line: &[u8]) -> Option<u16> {
+    String::from_utf8_lossy(line)
+        .trim()
+        .strip_prefix(READY_PREFIX)?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Re-emits a sidecar stdout/stderr line to the webview under `event`.
+fn emit_line(app: &AppHandle, name: &str, event: &str, line: &[u8]) {
+    let payload = SidecarLine {
+        name,
+        line: String::from_utf8_lossy(line).to_string(),
+    };
+    if let Err(err) = app.emit(event, payload) {
+        log::error!("failed to emit {event} for sidecar '{name}': {err}");
+    }
+}
+
+/// Logs and emits a fatal sidecar error to the frontend. Unlike a bare
+/// `log::error!`, the emit reaches the frontend even in release builds,
+/// where no logger is registered, so the app isn't left silently stuck on
+/// its hidden splash state.
+fn emit_fatal(app: &AppHandle, name: &str, message: impl Into<String>) {
+    let message = message.into();
+    log::error!("{message}");
+    if let Err(err) = app.emit(FATAL_EVENT, SidecarFatal { name, message }) {
+        log::error!("failed to emit {FATAL_EVENT} for sidecar '{name}': {err}");
+    }
+}
+
+/// Returns the port `name`'s sidecar reported as ready on, if it has started.
+#[tauri::command]
+pub fn sidecar_port(name: String, state: tauri::State<'_, SidecarState>) -> Option<u16> {
+    state.entry(&name).and_then(|entry| *entry.ready_port.lock().unwrap())
+}
+
+/// Writes a line to `name`'s sidecar stdin, letting the frontend push
+/// commands or config into the running backend.
+#[tauri::command]
+pub fn write_sidecar(
+    name: String,
+    input: String,
+    state: tauri::State<'_, SidecarState>,
+) -> Result<(), String> {
+    let entry = state
+        .entry(&name)
+        .ok_or_else(|| format!("unknown sidecar '{name}'"))?;
+    let mut child = entry.child.lock().unwrap();
+    let child = child
+        .as_mut()
+        .ok_or_else(|| format!("sidecar '{name}' is not running"))?;
+    child.write(input.as_bytes()).map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_ready_port;
+
+    #[test]
+    fn parses_a_ready_line() {
+        assert_eq!(parse_ready_port(b"READY 51823"), Some(51823));
+    }
+
+    #[test]
+    fn requires_the_space_after_the_prefix() {
+        assert_eq!(parse_ready_port(b"READY51823"), None);
+    }
+
+    #[test]
+    fn trims_a_trailing_carriage_return() {
+        assert_eq!(parse_ready_port(b"READY 51823\r\n"), Some(51823));
+    }
+
+    #[test]
+    fn rejects_a_port_that_overflows_u16() {
+        assert_eq!(parse_ready_port(b"READY 99999"), None);
+    }
+
+    #[test]
+    fn ignores_unrelated_lines() {
+        assert_eq!(parse_ready_port(b"listening on 8080"), None);
+    }
+}
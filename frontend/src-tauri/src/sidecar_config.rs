@@ -0,0 +1,86 @@
+//! Sidecar configuration, read at runtime from a bundled `sidecar.json`
+//! resource.
+//!
+//! Each entry describes one `externalBin` sidecar: its name, CLI args,
+//! environment, and whether it needs a free TCP port allocated and injected
+//! so multiple backends can run side by side without port clashes. Editing
+//! the bundled `sidecar.json` changes this without a rebuild; the embedded
+//! copy below only covers the case where that resource is missing,
+//! unreadable or invalid (e.g. a broken bundle).
+
+use std::collections::HashMap;
+use std::io;
+
+use serde::Deserialize;
+use tauri::path::BaseDirectory;
+use tauri::{AppHandle, Manager};
+
+/// Restart a sidecar at most this many consecutive times before giving up,
+/// unless `sidecar.json` overrides it per-sidecar.
+pub const DEFAULT_MAX_RESTART_ATTEMPTS: u32 = 10;
+
+fn default_max_restart_attempts() -> u32 {
+    DEFAULT_MAX_RESTART_ATTEMPTS
+}
+
+/// How a sidecar's assigned port should be passed to it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PortConfig {
+    /// CLI flag the port is passed under, e.g. `"port"` for `--port <n>`.
+    pub arg: String,
+    /// Environment variable the port is also exported as.
+    pub env: String,
+}
+
+/// One sidecar to spawn and supervise.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SidecarConfig {
+    /// Name registered as `externalBin` in `tauri.conf.json`.
+    pub name: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub port: Option<PortConfig>,
+    /// How many consecutive crash-restarts to attempt before giving up on
+    /// this sidecar. Defaults to [`DEFAULT_MAX_RESTART_ATTEMPTS`].
+    #[serde(default = "default_max_restart_attempts")]
+    pub max_restart_attempts: u32,
+}
+
+/// Top-level shape of `sidecar.json`.
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct SidecarsConfig {
+    #[serde(default)]
+    pub sidecars: Vec<SidecarConfig>,
+}
+
+/// Embedded fallback used only when the bundled `sidecar.json` resource
+/// can't be found, read or parsed.
+const FALLBACK_CONFIG: &str = include_str!("../sidecar.json");
+
+/// Loads `sidecar.json` from the app's bundled resources, falling back to
+/// the embedded default (a single `api` sidecar with an allocated port) if
+/// the resource is missing, unreadable, or fails to parse.
+pub fn load(app: &AppHandle) -> SidecarsConfig {
+    match read_bundled_config(app) {
+        Ok(contents) => match serde_json::from_str(&contents) {
+            Ok(config) => return config,
+            Err(err) => log::error!("failed to parse bundled sidecar.json: {err}"),
+        },
+        Err(err) => {
+            log::warn!("falling back to the built-in sidecar.json: {err}");
+        }
+    }
+
+    serde_json::from_str(FALLBACK_CONFIG).expect("embedded sidecar.json fallback is valid JSON")
+}
+
+fn read_bundled_config(app: &AppHandle) -> io::Result<String> {
+    let path = app
+        .path()
+        .resolve("sidecar.json", BaseDirectory::Resource)
+        .map_err(|err| io::Error::new(io::ErrorKind::NotFound, err))?;
+    std::fs::read_to_string(path)
+}